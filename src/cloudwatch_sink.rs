@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use aws_config::BehaviorVersion;
+use aws_sdk_cloudwatch::{
+    types::{Dimension, MetricDatum, StandardUnit, StatisticSet},
+    Client as CloudWatchClient,
+};
+use tracing::{info, warn};
+
+use crate::sink::{MetricSink, Sample};
+
+/// Maximum number of `MetricDatum` entries CloudWatch accepts per `PutMetricData` call.
+const CLOUDWATCH_MAX_DATUMS_PER_CALL: usize = 20;
+
+/// Accumulates per-tick samples into the min/max/sum/count shape CloudWatch's
+/// `StatisticSet` expects, so several ticks can be rolled up into one datum.
+#[derive(Debug, Default, Clone, Copy)]
+struct StatisticSetAccumulator {
+    minimum: f64,
+    maximum: f64,
+    sum: f64,
+    sample_count: f64,
+}
+
+impl StatisticSetAccumulator {
+    fn accumulate(&mut self, value: f64) {
+        if self.sample_count == 0.0 {
+            self.minimum = value;
+            self.maximum = value;
+        } else {
+            self.minimum = self.minimum.min(value);
+            self.maximum = self.maximum.max(value);
+        }
+        self.sum += value;
+        self.sample_count += 1.0;
+    }
+
+    fn into_statistic_set(self) -> StatisticSet {
+        StatisticSet::builder()
+            .minimum(self.minimum)
+            .maximum(self.maximum)
+            .sum(self.sum)
+            .sample_count(self.sample_count)
+            .build()
+    }
+}
+
+/// Per-container accumulators, keyed by metric name (`ListenQueue`, `MaxListenQueue`, ...).
+/// Entries are rolled up across several ticks and flushed every `publish_interval`, since
+/// `PutMetricData` is too expensive to call once per tick.
+type ContainerStats = HashMap<String, HashMap<&'static str, StatisticSetAccumulator>>;
+
+/// Publishes samples to CloudWatch, rolling several ticks' worth up into one
+/// `StatisticSet` per container per `publish_interval` rather than calling
+/// `PutMetricData` on every tick.
+pub struct CloudWatchSink {
+    client: CloudWatchClient,
+    namespace: String,
+    dimensions: Vec<Dimension>,
+    ticks_per_publish: u64,
+    ticks_since_publish: u64,
+    container_stats: ContainerStats,
+}
+
+impl CloudWatchSink {
+    pub async fn new(
+        region: Option<String>,
+        namespace: String,
+        dimensions: &[String],
+        interval_secs: u64,
+        publish_interval_secs: u64,
+    ) -> Result<Self> {
+        let config = if let Some(region) = region {
+            aws_config::defaults(BehaviorVersion::latest())
+                .region(aws_config::Region::new(region))
+                .load()
+                .await
+        } else {
+            aws_config::load_defaults(BehaviorVersion::latest()).await
+        };
+
+        Ok(Self {
+            client: CloudWatchClient::new(&config),
+            namespace,
+            dimensions: parse_dimensions(dimensions),
+            ticks_per_publish: (publish_interval_secs / interval_secs).max(1),
+            ticks_since_publish: 0,
+            container_stats: HashMap::new(),
+        })
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        if self.container_stats.is_empty() {
+            return Ok(());
+        }
+
+        let mut metric_data = Vec::new();
+
+        for (container_id, metrics) in &self.container_stats {
+            let mut dimensions = self.dimensions.clone();
+            if container_id != "_total" {
+                dimensions.push(Dimension::builder().name("ContainerId").value(container_id).build());
+            }
+
+            for (metric_name, stats) in metrics {
+                metric_data.push(
+                    MetricDatum::builder()
+                        .metric_name(*metric_name)
+                        .unit(StandardUnit::Count)
+                        .statistic_values(stats.into_statistic_set())
+                        .storage_resolution(1) // High resolution metric
+                        .set_dimensions(Some(dimensions.clone()))
+                        .build(),
+                );
+            }
+        }
+
+        // PutMetricData rejects more than 20 datums per call, so send sequentially in batches.
+        for chunk in metric_data.chunks(CLOUDWATCH_MAX_DATUMS_PER_CALL) {
+            self.client
+                .put_metric_data()
+                .namespace(&self.namespace)
+                .set_metric_data(Some(chunk.to_vec()))
+                .send()
+                .await
+                .context("Failed to send metric batch to CloudWatch")?;
+        }
+
+        info!("Sent rollup for {} container(s) to CloudWatch", self.container_stats.len());
+        self.container_stats.clear();
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MetricSink for CloudWatchSink {
+    async fn emit(&mut self, samples: &[Sample]) -> Result<()> {
+        for sample in samples {
+            let entry = self.container_stats.entry(sample.container_id.clone()).or_default();
+            entry.entry("ListenQueue").or_default().accumulate(sample.listen_queue as f64);
+            if let Some(v) = sample.max_listen_queue {
+                entry.entry("MaxListenQueue").or_default().accumulate(v as f64);
+            }
+            if let Some(v) = sample.active_processes {
+                entry.entry("ActiveProcesses").or_default().accumulate(v as f64);
+            }
+            if let Some(v) = sample.idle_processes {
+                entry.entry("IdleProcesses").or_default().accumulate(v as f64);
+            }
+            if let Some(v) = sample.slow_requests {
+                entry.entry("SlowRequests").or_default().accumulate(v as f64);
+            }
+        }
+
+        self.ticks_since_publish += 1;
+        if self.ticks_since_publish < self.ticks_per_publish {
+            return Ok(());
+        }
+        self.ticks_since_publish = 0;
+
+        self.flush().await
+    }
+}
+
+/// Parses CLI dimensions from `key=value` pairs.
+fn parse_dimensions(dimension_strs: &[String]) -> Vec<Dimension> {
+    let mut dimensions = Vec::new();
+
+    for dimension_str in dimension_strs {
+        if let Some((key, value)) = dimension_str.split_once('=') {
+            dimensions.push(Dimension::builder().name(key.trim()).value(value.trim()).build());
+        } else {
+            warn!("Invalid dimension format '{}', expected key=value", dimension_str);
+        }
+    }
+
+    dimensions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_sample_sets_min_and_max_to_itself() {
+        let mut acc = StatisticSetAccumulator::default();
+        acc.accumulate(5.0);
+
+        let stats = acc.into_statistic_set();
+        assert_eq!(stats.minimum(), Some(5.0));
+        assert_eq!(stats.maximum(), Some(5.0));
+        assert_eq!(stats.sum(), Some(5.0));
+        assert_eq!(stats.sample_count(), Some(1.0));
+    }
+
+    #[test]
+    fn accumulate_tracks_min_max_sum_and_count_across_samples() {
+        let mut acc = StatisticSetAccumulator::default();
+        for value in [5.0, 1.0, 9.0, 3.0] {
+            acc.accumulate(value);
+        }
+
+        let stats = acc.into_statistic_set();
+        assert_eq!(stats.minimum(), Some(1.0));
+        assert_eq!(stats.maximum(), Some(9.0));
+        assert_eq!(stats.sum(), Some(18.0));
+        assert_eq!(stats.sample_count(), Some(4.0));
+    }
+
+    #[test]
+    fn accumulate_handles_negative_and_zero_values() {
+        let mut acc = StatisticSetAccumulator::default();
+        acc.accumulate(-2.0);
+        acc.accumulate(0.0);
+
+        let stats = acc.into_statistic_set();
+        assert_eq!(stats.minimum(), Some(-2.0));
+        assert_eq!(stats.maximum(), Some(0.0));
+    }
+
+    #[test]
+    fn default_accumulator_with_no_samples_reports_zero_count() {
+        let acc = StatisticSetAccumulator::default();
+        let stats = acc.into_statistic_set();
+        assert_eq!(stats.sample_count(), Some(0.0));
+    }
+}
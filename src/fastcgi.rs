@@ -0,0 +1,279 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::UnixStream;
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+const FCGI_VERSION_1: u8 = 1;
+const FCGI_BEGIN_REQUEST: u8 = 1;
+const FCGI_PARAMS: u8 = 4;
+const FCGI_STDIN: u8 = 5;
+const FCGI_STDOUT: u8 = 6;
+const FCGI_END_REQUEST: u8 = 3;
+const FCGI_RESPONDER: u16 = 1;
+const FCGI_REQUEST_ID: u16 = 1;
+
+/// Parsed response from a PHP-FPM pool's `pm.status_path` endpoint (`?json`).
+/// Field names mirror the FPM status page's JSON keys; unknown fields (e.g.
+/// `pool`, `process manager`) are ignored.
+#[derive(Debug, Deserialize)]
+pub struct FpmStatus {
+    #[serde(rename = "listen queue")]
+    pub listen_queue: i32,
+    #[serde(rename = "max listen queue")]
+    pub max_listen_queue: i32,
+    #[serde(rename = "active processes")]
+    pub active_processes: i32,
+    #[serde(rename = "idle processes")]
+    pub idle_processes: i32,
+    #[serde(rename = "slow requests")]
+    pub slow_requests: i32,
+}
+
+/// Queries a PHP-FPM pool's FastCGI status page and returns the parsed metrics.
+///
+/// `pid` is the PID of a process inside the target container/pod, used to
+/// enter its network and mount namespaces (the unix socket lives in the
+/// container's filesystem). `socket_path` is the FastCGI socket path as seen
+/// from inside that namespace, and `status_path` is the pool's configured
+/// `pm.status_path` (e.g. `/status`).
+pub async fn query_fpm_status(pid: u32, socket_path: &str, status_path: &str) -> Result<FpmStatus> {
+    let request = build_status_request(status_path);
+    let socket_path = socket_path.to_string();
+    let status_path = status_path.to_string();
+
+    tokio::task::spawn_blocking(move || send_fastcgi_request(pid, &socket_path, &request))
+        .await
+        .context("FastCGI worker thread panicked")?
+        .and_then(|body| parse_status_response(&body, &status_path))
+}
+
+/// Connects to the FastCGI socket natively, by entering the target
+/// container's network and mount namespaces on this (dedicated, blocking)
+/// thread rather than shelling out to `nsenter`/`socat` - the same approach
+/// `sock_diag` uses for the socket_diag scrape, so this no longer needs a
+/// `sudo`/subprocess dependency of its own.
+fn send_fastcgi_request(pid: u32, socket_path: &str, request: &[u8]) -> Result<Vec<u8>> {
+    let original_net_ns = File::open("/proc/self/ns/net").context("Failed to open current netns")?;
+    let original_mnt_ns = File::open("/proc/self/ns/mnt").context("Failed to open current mount ns")?;
+    let target_net_ns = File::open(format!("/proc/{}/ns/net", pid))
+        .with_context(|| format!("Failed to open netns of pid {}", pid))?;
+    let target_mnt_ns = File::open(format!("/proc/{}/ns/mnt", pid))
+        .with_context(|| format!("Failed to open mount ns of pid {}", pid))?;
+
+    setns(&target_net_ns, libc::CLONE_NEWNET)
+        .with_context(|| format!("Failed to enter netns of pid {}", pid))?;
+    if let Err(e) = setns(&target_mnt_ns, libc::CLONE_NEWNS) {
+        if let Err(restore_err) = setns(&original_net_ns, libc::CLONE_NEWNET) {
+            panic!("Failed to restore original network namespace: {}", restore_err);
+        }
+        return Err(e.context(format!("Failed to enter mount ns of pid {}", pid)));
+    }
+
+    let result = query_over_socket(socket_path, request);
+
+    // Always attempt to restore the original namespaces, even on error; if we
+    // can't, the worker thread is stuck in the target namespaces, so treat it
+    // as fatal rather than silently querying the wrong container next tick.
+    if let Err(e) = setns(&original_mnt_ns, libc::CLONE_NEWNS) {
+        panic!("Failed to restore original mount namespace: {}", e);
+    }
+    if let Err(e) = setns(&original_net_ns, libc::CLONE_NEWNET) {
+        panic!("Failed to restore original network namespace: {}", e);
+    }
+
+    decode_stdout_stream(&result?)
+}
+
+fn setns(ns_file: &File, nstype: libc::c_int) -> Result<()> {
+    let ret = unsafe { libc::setns(ns_file.as_raw_fd(), nstype) };
+    if ret != 0 {
+        bail!("setns(2) failed: {}", std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn query_over_socket(socket_path: &str, request: &[u8]) -> Result<Vec<u8>> {
+    let mut stream = UnixStream::connect(socket_path)
+        .with_context(|| format!("Failed to connect to FastCGI socket {}", socket_path))?;
+
+    stream.write_all(request).context("Failed to write FastCGI request")?;
+    stream
+        .shutdown(std::net::Shutdown::Write)
+        .context("Failed to half-close FastCGI socket")?;
+
+    let mut response = Vec::new();
+    stream
+        .read_to_end(&mut response)
+        .context("Failed to read FastCGI response")?;
+
+    Ok(response)
+}
+
+/// Builds the FastCGI request that asks the FPM status page to render as JSON.
+fn build_status_request(status_path: &str) -> Vec<u8> {
+    let mut params = HashMap::new();
+    params.insert("REQUEST_METHOD".to_string(), "GET".to_string());
+    params.insert("SCRIPT_NAME".to_string(), status_path.to_string());
+    params.insert("SCRIPT_FILENAME".to_string(), status_path.to_string());
+    params.insert("QUERY_STRING".to_string(), "json".to_string());
+    params.insert("SERVER_PROTOCOL".to_string(), "HTTP/1.1".to_string());
+
+    let mut buf = Vec::new();
+    buf.extend(record_header(FCGI_BEGIN_REQUEST, 8));
+    buf.extend((FCGI_RESPONDER).to_be_bytes());
+    buf.push(0); // flags: do not keep connection alive
+    buf.extend([0u8; 5]); // reserved
+
+    let encoded_params = encode_params(&params);
+    buf.extend(record_header(FCGI_PARAMS, encoded_params.len() as u16));
+    buf.extend(encoded_params);
+    buf.extend(record_header(FCGI_PARAMS, 0)); // empty record terminates the stream
+
+    buf.extend(record_header(FCGI_STDIN, 0)); // no request body
+
+    buf
+}
+
+fn record_header(record_type: u8, content_length: u16) -> [u8; 8] {
+    let len = content_length.to_be_bytes();
+    [
+        FCGI_VERSION_1,
+        record_type,
+        (FCGI_REQUEST_ID >> 8) as u8,
+        (FCGI_REQUEST_ID & 0xff) as u8,
+        len[0],
+        len[1],
+        0, // padding length
+        0, // reserved
+    ]
+}
+
+fn encode_params(params: &HashMap<String, String>) -> Vec<u8> {
+    let mut buf = Vec::new();
+    for (key, value) in params {
+        encode_length(&mut buf, key.len());
+        encode_length(&mut buf, value.len());
+        buf.extend(key.as_bytes());
+        buf.extend(value.as_bytes());
+    }
+    buf
+}
+
+fn encode_length(buf: &mut Vec<u8>, len: usize) {
+    if len < 0x80 {
+        buf.push(len as u8);
+    } else {
+        buf.extend(((len as u32) | 0x8000_0000).to_be_bytes());
+    }
+}
+
+/// Strips FastCGI record framing from a FastCGI response, concatenating the
+/// content of every FCGI_STDOUT record until FCGI_END_REQUEST; records of
+/// other types (e.g. FCGI_STDERR) are skipped.
+fn decode_stdout_stream(raw: &[u8]) -> Result<Vec<u8>> {
+    let mut body = Vec::new();
+    let mut offset = 0;
+
+    while offset + 8 <= raw.len() {
+        let record_type = raw[offset + 1];
+        let content_length = u16::from_be_bytes([raw[offset + 4], raw[offset + 5]]) as usize;
+        let padding_length = raw[offset + 6] as usize;
+        let content_start = offset + 8;
+        let content_end = content_start + content_length;
+
+        anyhow::ensure!(content_end <= raw.len(), "Truncated FastCGI record");
+
+        if record_type == FCGI_STDOUT {
+            body.extend_from_slice(&raw[content_start..content_end]);
+        } else if record_type == FCGI_END_REQUEST {
+            break;
+        }
+
+        offset = content_end + padding_length;
+    }
+
+    Ok(body)
+}
+
+/// Splits the FastCGI response into its CGI headers and body, and parses the
+/// body as FPM status JSON.
+fn parse_status_response(raw: &[u8], status_path: &str) -> Result<FpmStatus> {
+    let response = String::from_utf8_lossy(raw);
+    let body = match response.split_once("\r\n\r\n") {
+        Some((_headers, body)) => body,
+        None => response.as_ref(),
+    };
+
+    serde_json::from_str(body)
+        .with_context(|| format!("Failed to parse FPM status JSON from {}", status_path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_length_below_boundary_is_a_single_byte() {
+        let mut buf = Vec::new();
+        encode_length(&mut buf, 0x7f);
+        assert_eq!(buf, vec![0x7f]);
+    }
+
+    #[test]
+    fn encode_length_at_boundary_is_four_bytes() {
+        let mut buf = Vec::new();
+        encode_length(&mut buf, 0x80);
+        assert_eq!(buf, vec![0x80, 0x00, 0x00, 0x80]);
+    }
+
+    #[test]
+    fn encode_length_large_value_sets_high_bit() {
+        let mut buf = Vec::new();
+        encode_length(&mut buf, 300);
+        assert_eq!(buf, vec![0x80, 0x00, 0x01, 0x2c]);
+    }
+
+    fn record(record_type: u8, content: &[u8]) -> Vec<u8> {
+        let mut buf = record_header(record_type, content.len() as u16).to_vec();
+        buf.extend_from_slice(content);
+        buf
+    }
+
+    #[test]
+    fn decode_stdout_stream_concatenates_multiple_stdout_records() {
+        let mut raw = record(FCGI_STDOUT, b"hello ");
+        raw.extend(record(FCGI_STDOUT, b"world"));
+        raw.extend(record(FCGI_END_REQUEST, &[]));
+
+        let body = decode_stdout_stream(&raw).unwrap();
+        assert_eq!(body, b"hello world");
+    }
+
+    #[test]
+    fn decode_stdout_stream_stops_at_end_request() {
+        let mut raw = record(FCGI_STDOUT, b"before");
+        raw.extend(record(FCGI_END_REQUEST, &[]));
+        raw.extend(record(FCGI_STDOUT, b"after"));
+
+        let body = decode_stdout_stream(&raw).unwrap();
+        assert_eq!(body, b"before");
+    }
+
+    #[test]
+    fn decode_stdout_stream_errors_on_truncated_record() {
+        let mut raw = record_header(FCGI_STDOUT, 10).to_vec();
+        raw.extend_from_slice(b"short"); // fewer than the declared 10 content bytes
+
+        assert!(decode_stdout_stream(&raw).is_err());
+    }
+
+    #[test]
+    fn decode_stdout_stream_empty_input_is_empty_body() {
+        let body = decode_stdout_stream(&[]).unwrap();
+        assert!(body.is_empty());
+    }
+}
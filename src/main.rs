@@ -1,38 +1,195 @@
 use anyhow::{Context, Result};
-use aws_config::BehaviorVersion;
-use aws_sdk_cloudwatch::{
-    types::{Dimension, MetricDatum, StandardUnit},
-    Client as CloudWatchClient,
-};
-use clap::Parser;
-use serde_json::Value;
-use std::process::Command;
-use std::time::Duration;
+use clap::{Parser, ValueEnum};
+use std::net::SocketAddr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::time;
-use tracing::{error, info, warn};
+use tracing::{error, info};
+
+#[cfg(feature = "cloudwatch")]
+mod cloudwatch_sink;
+mod fastcgi;
+mod metrics;
+mod runtime;
+mod sink;
+mod sock_diag;
+
+#[cfg(feature = "cloudwatch")]
+use cloudwatch_sink::CloudWatchSink;
+use metrics::PrometheusMetrics;
+use runtime::{ContainerHandle, ContainerRuntime, ContainerdRuntime, DockerRuntime};
+use sink::{FileSink, MetricSink, Sample, StdoutSink};
+
+/// Where to source queue metrics from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Source {
+    /// Read the receive-queue length of the pool's listen socket with a netlink `sock_diag` query.
+    Ss,
+    /// Query the pool's FastCGI status page (`pm.status_path`) with `?json`.
+    FpmStatus,
+}
+
+/// Which daemon to use for container discovery.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Runtime {
+    /// Shell out to the `docker` CLI (`docker ps`/`docker inspect`).
+    Docker,
+    /// Talk to containerd's native gRPC API over its socket.
+    Containerd,
+}
+
+/// Where to publish collected samples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Output {
+    /// Roll samples up and push them to CloudWatch. Requires the `cloudwatch` feature.
+    Cloudwatch,
+    /// Print newline-delimited JSON records to stdout.
+    Stdout,
+    /// Append newline-delimited JSON records to `--output-file`.
+    File,
+}
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// Interval in seconds to run the monitoring loop
+    /// Interval in seconds to poll container queue lengths
     #[arg(short, long, default_value_t = 10)]
     interval: u64,
 
-    /// AWS region (defaults to environment variable or AWS config)
+    /// Interval in seconds between CloudWatch publishes (samples are rolled up across ticks)
+    #[arg(short, long, default_value_t = 60)]
+    publish_interval: u64,
+
+    /// Where to publish collected samples
+    #[arg(short, long, value_enum, default_value_t = Output::Cloudwatch)]
+    output: Output,
+
+    /// Path to append newline-delimited JSON records to, used when `--output file` is set
+    #[arg(long, default_value = "php-fpm-queue-monitor.jsonl")]
+    output_file: String,
+
+    /// AWS region (defaults to environment variable or AWS config), used when
+    /// `--output cloudwatch` is set
+    #[cfg(feature = "cloudwatch")]
     #[arg(short, long)]
     region: Option<String>,
 
-    /// CloudWatch namespace for metrics
+    /// CloudWatch namespace for metrics, used when `--output cloudwatch` is set
+    #[cfg(feature = "cloudwatch")]
     #[arg(short, long, default_value = "PhpFpm")]
     namespace: String,
 
-    /// Dimensions for metrics as key=value pairs (can be specified multiple times)
+    /// Dimensions for metrics as key=value pairs (can be specified multiple times),
+    /// used when `--output cloudwatch` is set
+    #[cfg(feature = "cloudwatch")]
     #[arg(short, long)]
     dimension: Vec<String>,
 
-    /// Dry run mode - don't send metrics to CloudWatch
+    /// Dry run mode - use the stdout sink instead of `--output`
     #[arg(long)]
     dry_run: bool,
+
+    /// Address to serve a Prometheus `/metrics` scrape endpoint on, e.g. `0.0.0.0:9898`.
+    /// When set, the monitoring loop updates this alongside the selected sink.
+    #[arg(long)]
+    prometheus_listen: Option<SocketAddr>,
+
+    /// Where to source queue metrics from
+    #[arg(long, value_enum, default_value_t = Source::Ss)]
+    source: Source,
+
+    /// Glob matching the PHP-FPM socket path, as seen from inside the container's
+    /// network namespace (e.g. `/var/run/php-fpm/*.socket`). With `--source
+    /// fpm-status` the value is connected to as a literal path; glob patterns
+    /// are not expanded.
+    #[arg(long, default_value = "/var/run/php-fpm/www.socket")]
+    socket_path: String,
+
+    /// PHP-FPM `pm.status_path`, used when `--source fpm-status` is set
+    #[arg(long, default_value = "/status")]
+    status_path: String,
+
+    /// Container runtime to discover containers through
+    #[arg(long, value_enum, default_value_t = Runtime::Docker)]
+    runtime: Runtime,
+
+    /// Path to the containerd socket, used when `--runtime containerd` is set
+    #[arg(long, default_value = "/run/containerd/containerd.sock")]
+    containerd_socket: String,
+
+    /// containerd namespace to list containers in, used when `--runtime
+    /// containerd` is set. Kubernetes nodes run their pods' containers under
+    /// the `k8s.io` namespace; a bare containerd install typically uses
+    /// `default`.
+    #[arg(long, default_value = "k8s.io")]
+    containerd_namespace: String,
+
+    /// Pod label or annotation (`key=value`) that marks a container as a PHP-FPM
+    /// pool, in addition to the command-line check. Only consulted
+    /// by `--runtime containerd`, where pools are often tagged explicitly.
+    #[arg(long)]
+    php_fpm_label: Option<String>,
+}
+
+fn build_runtime(args: &Args) -> Result<Box<dyn ContainerRuntime>> {
+    match args.runtime {
+        Runtime::Docker => Ok(Box::new(DockerRuntime)),
+        Runtime::Containerd => Ok(Box::new(ContainerdRuntime::new(
+            args.containerd_socket.clone(),
+            args.containerd_namespace.clone(),
+            args.php_fpm_label.clone(),
+        )?)),
+    }
+}
+
+async fn build_sink(args: &Args) -> Result<Box<dyn MetricSink>> {
+    // --dry-run predates --output and just means "use the stdout sink".
+    let output = if args.dry_run { Output::Stdout } else { args.output };
+
+    match output {
+        Output::Stdout => Ok(Box::new(StdoutSink)),
+        Output::File => Ok(Box::new(FileSink::open(&args.output_file).await?)),
+        #[cfg(feature = "cloudwatch")]
+        Output::Cloudwatch => Ok(Box::new(
+            CloudWatchSink::new(
+                args.region.clone(),
+                args.namespace.clone(),
+                &args.dimension,
+                args.interval,
+                args.publish_interval,
+            )
+            .await?,
+        )),
+        #[cfg(not(feature = "cloudwatch"))]
+        Output::Cloudwatch => {
+            anyhow::bail!("--output cloudwatch requires building with the `cloudwatch` feature enabled")
+        }
+    }
+}
+
+/// A single tick's reading for one container. `listen_queue` is always
+/// populated (from either source); the FPM-status-only fields are `None`
+/// when collected via `--source ss`.
+#[derive(Debug, Default, Clone, Copy)]
+struct QueueSample {
+    listen_queue: i32,
+    max_listen_queue: Option<i32>,
+    active_processes: Option<i32>,
+    idle_processes: Option<i32>,
+    slow_requests: Option<i32>,
+}
+
+impl QueueSample {
+    fn into_sample(self, ts: i64, container_id: String) -> Sample {
+        Sample {
+            ts,
+            container_id,
+            listen_queue: self.listen_queue,
+            max_listen_queue: self.max_listen_queue,
+            active_processes: self.active_processes,
+            idle_processes: self.idle_processes,
+            slow_requests: self.slow_requests,
+        }
+    }
 }
 
 #[tokio::main]
@@ -43,204 +200,103 @@ async fn main() -> Result<()> {
 
     info!("Starting PHP-FPM queue monitor");
     info!("Interval: {} seconds", args.interval);
-    info!("Namespace: {}", args.namespace);
+    info!("Publish interval: {} seconds", args.publish_interval);
+    info!("Output: {:?}", args.output);
     info!("Dry run: {}", args.dry_run);
+    info!("Source: {:?}", args.source);
 
-    // Initialize AWS config
-    let config = if let Some(region) = &args.region {
-        aws_config::defaults(BehaviorVersion::latest())
-            .region(aws_config::Region::new(region.clone()))
-            .load()
-            .await
-    } else {
-        aws_config::load_defaults(BehaviorVersion::latest()).await
-    };
+    let container_runtime = build_runtime(&args)?;
+    let mut sink = build_sink(&args).await?;
 
-    let cloudwatch = CloudWatchClient::new(&config);
+    let prometheus_metrics = PrometheusMetrics::new().context("Failed to initialize Prometheus registry")?;
+    if let Some(addr) = args.prometheus_listen {
+        metrics::spawn_server(addr, prometheus_metrics.clone());
+    }
 
     let mut interval = time::interval(Duration::from_secs(args.interval));
 
     loop {
         interval.tick().await;
 
-        match collect_and_send_metrics(&cloudwatch, &args).await {
-            Ok(()) => {}
+        match collect_php_fpm_queue_length(container_runtime.as_ref(), &args).await {
+            Ok(per_container) => {
+                let ts = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs() as i64)
+                    .unwrap_or(0);
+                let total_queue_len: i32 = per_container.iter().map(|(_, sample)| sample.listen_queue).sum();
+                info!("Total queue length: {}", total_queue_len);
+
+                let mut samples: Vec<Sample> = per_container
+                    .iter()
+                    .map(|(container_id, sample)| {
+                        prometheus_metrics.observe_queue_length(container_id, sample.listen_queue);
+                        sample.into_sample(ts, container_id.clone())
+                    })
+                    .collect();
+                samples.push(QueueSample { listen_queue: total_queue_len, ..Default::default() }.into_sample(ts, "_total".to_string()));
+
+                if let Err(e) = sink.emit(&samples).await {
+                    error!("Error emitting samples: {}", e);
+                }
+            }
             Err(e) => {
-                error!("Error in monitoring loop: {}", e);
+                error!("Error collecting queue lengths: {}", e);
+                prometheus_metrics.record_poll_error();
             }
         }
     }
 }
 
-async fn collect_and_send_metrics(
-    cloudwatch: &CloudWatchClient,
+async fn collect_php_fpm_queue_length(
+    container_runtime: &dyn ContainerRuntime,
     args: &Args,
-) -> Result<()> {
-    let total_queue_len = collect_php_fpm_queue_length().await?;
-
-    info!("Total queue length: {}", total_queue_len);
-
-    // Only send metrics if queue length is greater than 0
-    if total_queue_len > 0 {
-        if args.dry_run {
-            info!("Would send metric: {} to namespace {}", total_queue_len, args.namespace);
-        } else {
-            send_cloudwatch_metric(cloudwatch, args, total_queue_len).await?;
-            info!("Sent metric to CloudWatch: {}", total_queue_len);
-        }
-    }
-
-    Ok(())
-}
-
-async fn collect_php_fpm_queue_length() -> Result<i32> {
-    let container_ids = get_docker_container_ids().await?;
-    let mut total_queue_len = 0;
-
-    for container_id in container_ids {
-        if is_php_fpm_container(&container_id).await? {
-            let queue_len = get_container_queue_length(&container_id).await?;
-            total_queue_len += queue_len;
+) -> Result<Vec<(String, QueueSample)>> {
+    let handles = container_runtime.list_containers().await?;
+    let mut samples = Vec::new();
+
+    for handle in handles {
+        if container_runtime.is_php_fpm(&handle).await? {
+            let pid = container_runtime.pid_of(&handle).await?;
+            let sample = match args.source {
+                Source::Ss => get_container_queue_length(&handle, pid, &args.socket_path).await?,
+                Source::FpmStatus => {
+                    get_container_fpm_status(&handle, pid, &args.socket_path, &args.status_path).await?
+                }
+            };
+            samples.push((handle.id, sample));
         }
     }
 
-    Ok(total_queue_len)
-}
-
-async fn get_docker_container_ids() -> Result<Vec<String>> {
-    let output = Command::new("docker")
-        .args(["ps", "-q"])
-        .output()
-        .context("Failed to execute docker ps -q")?;
-
-    if !output.status.success() {
-        anyhow::bail!("docker ps -q failed with status: {}", output.status);
-    }
-
-    let container_ids: Vec<String> = String::from_utf8(output.stdout)?
-        .lines()
-        .filter(|line| !line.trim().is_empty())
-        .map(|line| line.trim().to_string())
-        .collect();
-
-    Ok(container_ids)
-}
-
-async fn is_php_fpm_container(container_id: &str) -> Result<bool> {
-    let output = Command::new("docker")
-        .args(["inspect", container_id, "--format", "{{json .Config.Cmd}}"])
-        .output()
-        .context("Failed to execute docker inspect")?;
-
-    if !output.status.success() {
-        warn!("Failed to inspect container {}: {}", container_id, output.status);
-        return Ok(false);
-    }
-
-    let cmd_json = String::from_utf8(output.stdout)?;
-    let cmd: Value = serde_json::from_str(&cmd_json.trim())
-        .context("Failed to parse docker inspect output as JSON")?;
-
-    // Check if the command array contains "php-fpm"
-    if let Some(cmd_array) = cmd.as_array() {
-        Ok(cmd_array.iter().any(|v| {
-            v.as_str().map_or(false, |s| s == "php-fpm")
-        }))
-    } else {
-        Ok(false)
-    }
+    Ok(samples)
 }
 
-async fn get_container_queue_length(container_id: &str) -> Result<i32> {
-    // First get the container PID
-    let pid_output = Command::new("docker")
-        .args(["inspect", "-f", "{{.State.Pid}}", container_id])
-        .output()
-        .context("Failed to get container PID")?;
-
-    if !pid_output.status.success() {
-        anyhow::bail!("Failed to get PID for container {}", container_id);
-    }
-
-    let pid = String::from_utf8(pid_output.stdout)?
-        .trim()
-        .parse::<u32>()
-        .context("Failed to parse PID")?;
-
-    // Use nsenter and ss to get socket queue information
-    let output = Command::new("sudo")
-        .args([
-            "nsenter",
-            "-t",
-            &pid.to_string(),
-            "-n",
-            "ss",
-            "-lxnH",
-        ])
-        .output()
-        .context("Failed to execute nsenter ss command")?;
-
-    if !output.status.success() {
-        warn!("nsenter ss failed for container {}: {}", container_id, output.status);
-        return Ok(0);
-    }
-
-    let ss_output = String::from_utf8(output.stdout)?;
-
-    // Parse the ss output to find the PHP-FPM socket and extract queue length
-    for line in ss_output.lines() {
-        if line.contains("/var/run/php-fpm/www.socket") {
-            // Extract the third column (queue length) from ss output
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() >= 3 {
-                if let Ok(queue_len) = parts[2].parse::<i32>() {
-                    return Ok(queue_len);
-                }
-            }
-        }
-    }
+async fn get_container_queue_length(handle: &ContainerHandle, pid: u32, socket_path_glob: &str) -> Result<QueueSample> {
+    let queue_len = sock_diag::queue_length_for_pid(pid, socket_path_glob)
+        .await
+        .with_context(|| format!("Failed to read socket queue length for container {}", handle.id))?;
 
-    Ok(0)
+    Ok(QueueSample {
+        listen_queue: queue_len,
+        ..Default::default()
+    })
 }
 
-async fn send_cloudwatch_metric(
-    cloudwatch: &CloudWatchClient,
-    args: &Args,
-    value: i32,
-) -> Result<()> {
-    let mut dimensions = Vec::new();
-
-    // Parse dimensions from CLI arguments (format: key=value)
-    for dimension_str in &args.dimension {
-        if let Some((key, value)) = dimension_str.split_once('=') {
-            dimensions.push(
-                Dimension::builder()
-                    .name(key.trim())
-                    .value(value.trim())
-                    .build()
-            );
-        } else {
-            warn!("Invalid dimension format '{}', expected key=value", dimension_str);
-        }
-    }
-
-    let metric_datum = MetricDatum::builder()
-        .metric_name("ListenQueue")
-        .unit(StandardUnit::Count)
-        .value(value as f64)
-        .storage_resolution(1) // High resolution metric
-        .set_dimensions(Some(dimensions))
-        .build();
-
-    info!("Prepared MetricDatum: {:?}", metric_datum);
-
-    cloudwatch
-        .put_metric_data()
-        .namespace(&args.namespace)
-        .metric_data(metric_datum)
-        .send()
+async fn get_container_fpm_status(
+    handle: &ContainerHandle,
+    pid: u32,
+    socket_path: &str,
+    status_path: &str,
+) -> Result<QueueSample> {
+    let status = fastcgi::query_fpm_status(pid, socket_path, status_path)
         .await
-        .context("Failed to send metric to CloudWatch")?;
-
-    Ok(())
+        .with_context(|| format!("Failed to query FPM status for container {}", handle.id))?;
+
+    Ok(QueueSample {
+        listen_queue: status.listen_queue,
+        max_listen_queue: Some(status.max_listen_queue),
+        active_processes: Some(status.active_processes),
+        idle_processes: Some(status.idle_processes),
+        slow_requests: Some(status.slow_requests),
+    })
 }
@@ -0,0 +1,117 @@
+use std::convert::Infallible;
+use std::net::SocketAddr;
+
+use anyhow::{Context, Result};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server};
+use prometheus::{Encoder, GaugeVec, IntCounter, Opts, Registry, TextEncoder};
+use tracing::{error, info};
+
+/// Shared Prometheus registry updated by the monitoring loop on every tick and
+/// scraped by the `/metrics` HTTP endpoint. Cloned handles share the same
+/// underlying registry and metrics, so the collection logic and the HTTP
+/// server can run independently of each other.
+#[derive(Clone)]
+pub struct PrometheusMetrics {
+    registry: Registry,
+    listen_queue: GaugeVec,
+    poll_errors: IntCounter,
+}
+
+impl PrometheusMetrics {
+    pub fn new() -> Result<Self> {
+        let registry = Registry::new();
+
+        let listen_queue = GaugeVec::new(
+            Opts::new(
+                "php_fpm_listen_queue",
+                "Current PHP-FPM listen-queue depth per container",
+            ),
+            &["container_id"],
+        )
+        .context("Failed to create php_fpm_listen_queue gauge")?;
+        registry
+            .register(Box::new(listen_queue.clone()))
+            .context("Failed to register php_fpm_listen_queue gauge")?;
+
+        let poll_errors = IntCounter::new(
+            "php_fpm_queue_monitor_poll_errors_total",
+            "Number of errors encountered while scraping PHP-FPM queue lengths",
+        )
+        .context("Failed to create php_fpm_queue_monitor_poll_errors_total counter")?;
+        registry
+            .register(Box::new(poll_errors.clone()))
+            .context("Failed to register php_fpm_queue_monitor_poll_errors_total counter")?;
+
+        Ok(Self {
+            registry,
+            listen_queue,
+            poll_errors,
+        })
+    }
+
+    /// Records the current listen-queue depth for a container.
+    pub fn observe_queue_length(&self, container_id: &str, queue_len: i32) {
+        self.listen_queue
+            .with_label_values(&[container_id])
+            .set(queue_len as f64);
+    }
+
+    /// Increments the scrape/poll error counter.
+    pub fn record_poll_error(&self) {
+        self.poll_errors.inc();
+    }
+
+    fn encode(&self) -> Result<Vec<u8>> {
+        let mut buffer = Vec::new();
+        let encoder = TextEncoder::new();
+        let metric_families = self.registry.gather();
+        encoder
+            .encode(&metric_families, &mut buffer)
+            .context("Failed to encode Prometheus metrics")?;
+        Ok(buffer)
+    }
+}
+
+async fn serve_metrics(metrics: PrometheusMetrics, req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    if req.uri().path() != "/metrics" {
+        return Ok(Response::builder()
+            .status(404)
+            .body(Body::from("not found"))
+            .unwrap());
+    }
+
+    match metrics.encode() {
+        Ok(buffer) => Ok(Response::builder()
+            .header("Content-Type", "text/plain; version=0.0.4")
+            .body(Body::from(buffer))
+            .unwrap()),
+        Err(e) => {
+            error!("Failed to encode Prometheus metrics: {}", e);
+            Ok(Response::builder()
+                .status(500)
+                .body(Body::from("failed to encode metrics"))
+                .unwrap())
+        }
+    }
+}
+
+/// Spawns the `/metrics` HTTP server in the background. The spawned task
+/// ends only if the server exits, which should not happen in normal
+/// operation; no handle to it is returned.
+pub fn spawn_server(addr: SocketAddr, metrics: PrometheusMetrics) {
+    tokio::spawn(async move {
+        let make_svc = make_service_fn(move |_conn| {
+            let metrics = metrics.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| serve_metrics(metrics.clone(), req)))
+            }
+        });
+
+        info!("Prometheus metrics listening on http://{}/metrics", addr);
+
+        if let Err(e) = Server::bind(&addr).serve(make_svc).await {
+            error!("Prometheus metrics server error: {}", e);
+        }
+    });
+}
@@ -0,0 +1,231 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::process::Command;
+use tokio::sync::Mutex;
+use tracing::warn;
+
+use containerd_client::services::v1::{
+    containers_client::ContainersClient, tasks_client::TasksClient, Container, GetRequest,
+    ListContainersRequest,
+};
+use containerd_client::tonic::transport::Channel;
+use containerd_client::tonic::Request;
+use containerd_client::with_namespace;
+
+/// A container as seen by whichever `ContainerRuntime` discovered it. Holding
+/// only the opaque runtime-specific ID keeps this decoupled from Docker's or
+/// containerd's own container/pod types.
+#[derive(Debug, Clone)]
+pub struct ContainerHandle {
+    pub id: String,
+}
+
+/// Discovers running containers and answers the two questions the rest of the
+/// monitor needs about each one: is it a PHP-FPM pool, and what PID does the
+/// namespace-entering queue logic need? Implementations hide the daemon used
+/// to enumerate containers (Docker, containerd, ...) behind this trait.
+#[async_trait]
+pub trait ContainerRuntime: Send + Sync {
+    async fn list_containers(&self) -> Result<Vec<ContainerHandle>>;
+    async fn is_php_fpm(&self, handle: &ContainerHandle) -> Result<bool>;
+    async fn pid_of(&self, handle: &ContainerHandle) -> Result<u32>;
+}
+
+/// The original discovery path: shells out to the `docker` CLI.
+pub struct DockerRuntime;
+
+#[async_trait]
+impl ContainerRuntime for DockerRuntime {
+    async fn list_containers(&self) -> Result<Vec<ContainerHandle>> {
+        let output = Command::new("docker")
+            .args(["ps", "-q"])
+            .output()
+            .context("Failed to execute docker ps -q")?;
+
+        if !output.status.success() {
+            anyhow::bail!("docker ps -q failed with status: {}", output.status);
+        }
+
+        let handles = String::from_utf8(output.stdout)?
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| ContainerHandle { id: line.trim().to_string() })
+            .collect();
+
+        Ok(handles)
+    }
+
+    async fn is_php_fpm(&self, handle: &ContainerHandle) -> Result<bool> {
+        let output = Command::new("docker")
+            .args(["inspect", &handle.id, "--format", "{{json .Config.Cmd}}"])
+            .output()
+            .context("Failed to execute docker inspect")?;
+
+        if !output.status.success() {
+            warn!("Failed to inspect container {}: {}", handle.id, output.status);
+            return Ok(false);
+        }
+
+        let cmd_json = String::from_utf8(output.stdout)?;
+        let cmd: Value = serde_json::from_str(cmd_json.trim())
+            .context("Failed to parse docker inspect output as JSON")?;
+
+        Ok(cmd
+            .as_array()
+            .map(|cmd_array| cmd_array.iter().any(|v| v.as_str() == Some("php-fpm")))
+            .unwrap_or(false))
+    }
+
+    async fn pid_of(&self, handle: &ContainerHandle) -> Result<u32> {
+        let pid_output = Command::new("docker")
+            .args(["inspect", "-f", "{{.State.Pid}}", &handle.id])
+            .output()
+            .context("Failed to get container PID")?;
+
+        if !pid_output.status.success() {
+            anyhow::bail!("Failed to get PID for container {}", handle.id);
+        }
+
+        String::from_utf8(pid_output.stdout)?
+            .trim()
+            .parse::<u32>()
+            .context("Failed to parse PID")
+    }
+}
+
+/// Discovery via containerd's native gRPC API, for containerd-only and
+/// Kubernetes nodes where there is no `docker` binary to shell out to.
+pub struct ContainerdRuntime {
+    socket_path: String,
+    namespace: String,
+    /// Container label (`key=value`) that, if present, marks a container as a
+    /// PHP-FPM pool regardless of its command line. Under Kubernetes, the CRI
+    /// plugin carries pod labels/annotations through onto the container's
+    /// `labels` map, letting operators tag pools explicitly.
+    php_fpm_label: Option<(String, String)>,
+    /// Metadata for every container returned by the last `list_containers`
+    /// call, keyed by id. `is_php_fpm` reads from here instead of issuing its
+    /// own `Containers.Get` RPC per container.
+    containers: Mutex<HashMap<String, Container>>,
+}
+
+impl ContainerdRuntime {
+    pub fn new(socket_path: String, namespace: String, php_fpm_label: Option<String>) -> Result<Self> {
+        let php_fpm_label = php_fpm_label
+            .map(|label| {
+                label
+                    .split_once('=')
+                    .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+                    .with_context(|| format!("Invalid label format '{}', expected key=value", label))
+            })
+            .transpose()?;
+
+        Ok(Self {
+            socket_path,
+            namespace,
+            php_fpm_label,
+            containers: Mutex::new(HashMap::new()),
+        })
+    }
+
+    async fn connect(&self) -> Result<Channel> {
+        containerd_client::connect(&self.socket_path)
+            .await
+            .context("Failed to connect to containerd socket")
+    }
+}
+
+#[async_trait]
+impl ContainerRuntime for ContainerdRuntime {
+    async fn list_containers(&self) -> Result<Vec<ContainerHandle>> {
+        let mut client = ContainersClient::new(self.connect().await?);
+
+        let req = ListContainersRequest { filters: vec![] };
+        let request = with_namespace!(req, self.namespace);
+        let response = client
+            .list(request)
+            .await
+            .context("Failed to list containers via containerd")?
+            .into_inner();
+
+        // Rebuilding the cache from this tick's listing (rather than merging)
+        // means a container that stopped since the last tick also drops out
+        // of `is_php_fpm`'s view, instead of serving stale metadata for it.
+        let mut cache = self.containers.lock().await;
+        cache.clear();
+
+        let handles = response
+            .containers
+            .into_iter()
+            .map(|container| {
+                let handle = ContainerHandle { id: container.id.clone() };
+                cache.insert(container.id.clone(), container);
+                handle
+            })
+            .collect();
+
+        Ok(handles)
+    }
+
+    async fn is_php_fpm(&self, handle: &ContainerHandle) -> Result<bool> {
+        let cache = self.containers.lock().await;
+        let container = cache
+            .get(&handle.id)
+            .with_context(|| format!("No cached metadata for container {}; was list_containers called first?", handle.id))?;
+
+        if let Some((key, value)) = &self.php_fpm_label {
+            if container.labels.get(key).map(String::as_str) == Some(value.as_str()) {
+                return Ok(true);
+            }
+        }
+
+        // Match the exact "php-fpm" argv entry DockerRuntime looks for, rather
+        // than a broad image-name substring that would also catch unrelated
+        // images (e.g. "phpmyadmin-fpm-tools").
+        match &container.spec {
+            Some(spec) => command_is_php_fpm(&spec.value),
+            None => Ok(false),
+        }
+    }
+
+    async fn pid_of(&self, handle: &ContainerHandle) -> Result<u32> {
+        let mut client = TasksClient::new(self.connect().await?);
+
+        let req = GetRequest {
+            container_id: handle.id.clone(),
+            exec_id: String::new(),
+        };
+        let request = with_namespace!(req, self.namespace);
+        let response = client
+            .get(request)
+            .await
+            .context("Failed to get containerd task")?
+            .into_inner();
+
+        let process = response
+            .process
+            .with_context(|| format!("containerd task for {} did not include a process", handle.id))?;
+
+        Ok(process.pid)
+    }
+}
+
+/// Parses a container's OCI runtime spec (stored as JSON bytes in
+/// containerd's `Container.spec` field) and checks whether any entry of its
+/// `process.args` is exactly `php-fpm`, mirroring the argv check `DockerRuntime::is_php_fpm`
+/// does against `docker inspect`.
+fn command_is_php_fpm(spec_json: &[u8]) -> Result<bool> {
+    let spec: Value =
+        serde_json::from_slice(spec_json).context("Failed to parse OCI runtime spec JSON")?;
+
+    let args = spec
+        .get("process")
+        .and_then(|process| process.get("args"))
+        .and_then(Value::as_array);
+
+    Ok(args
+        .map(|args| args.iter().any(|v| v.as_str() == Some("php-fpm")))
+        .unwrap_or(false))
+}
@@ -0,0 +1,75 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Serialize;
+
+/// One container's (or the fleet aggregate's) reading for a single tick.
+/// `container_id` is `"_total"` for the cross-container aggregate. The
+/// FPM-status-only fields are `None` when collected via `--source ss`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Sample {
+    pub ts: i64,
+    pub container_id: String,
+    pub listen_queue: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_listen_queue: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub active_processes: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub idle_processes: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slow_requests: Option<i32>,
+}
+
+/// A destination for collected samples. Implementations decide how (and how
+/// often) samples actually get published; `emit` is called once per tick
+/// with that tick's samples.
+#[async_trait]
+pub trait MetricSink: Send + Sync {
+    async fn emit(&mut self, samples: &[Sample]) -> Result<()>;
+}
+
+/// Prints newline-delimited JSON records to stdout. This is what `--dry-run`
+/// selects.
+pub struct StdoutSink;
+
+#[async_trait]
+impl MetricSink for StdoutSink {
+    async fn emit(&mut self, samples: &[Sample]) -> Result<()> {
+        for sample in samples {
+            println!("{}", serde_json::to_string(sample)?);
+        }
+        Ok(())
+    }
+}
+
+/// Appends the same newline-delimited JSON records `StdoutSink` prints to a
+/// file instead.
+pub struct FileSink {
+    file: tokio::fs::File,
+}
+
+impl FileSink {
+    pub async fn open(path: &str) -> Result<Self> {
+        let file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+        Ok(Self { file })
+    }
+}
+
+#[async_trait]
+impl MetricSink for FileSink {
+    async fn emit(&mut self, samples: &[Sample]) -> Result<()> {
+        use tokio::io::AsyncWriteExt;
+
+        let mut buf = String::new();
+        for sample in samples {
+            buf.push_str(&serde_json::to_string(sample)?);
+            buf.push('\n');
+        }
+        self.file.write_all(buf.as_bytes()).await?;
+        Ok(())
+    }
+}
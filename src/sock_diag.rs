@@ -0,0 +1,313 @@
+use std::fs::File;
+use std::mem;
+use std::os::unix::io::AsRawFd;
+
+use anyhow::{bail, Context, Result};
+use glob::Pattern;
+
+// linux/unix_diag.h
+const UDIAG_SHOW_NAME: u32 = 0x01;
+const UDIAG_SHOW_RQLEN: u32 = 0x10;
+const UNIX_DIAG_NAME: u16 = 0;
+const UNIX_DIAG_RQLEN: u16 = 4;
+
+// linux/sock_diag.h
+const SOCK_DIAG_BY_FAMILY: u16 = 20;
+
+// Socket states we care about (include/net/tcp_states.h); PHP-FPM's pool
+// socket is always in LISTEN.
+const TCP_LISTEN: u32 = 10;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct UnixDiagReq {
+    sdiag_family: u8,
+    sdiag_protocol: u8,
+    pad: u16,
+    udiag_states: u32,
+    udiag_ino: u32,
+    udiag_show: u32,
+    udiag_cookie: [u32; 2],
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct UnixDiagMsg {
+    udiag_family: u8,
+    udiag_type: u8,
+    udiag_state: u8,
+    pad: u8,
+    udiag_ino: u32,
+    udiag_cookie: [u32; 2],
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct UnixDiagRqlen {
+    udiag_rqueue: u32,
+    udiag_wqueue: u32,
+}
+
+/// Reads the receive-queue length of the listening unix socket matching
+/// `socket_path_glob` inside the network namespace of `pid`, using a native
+/// `NETLINK_SOCK_DIAG` query instead of forking `nsenter ss`.
+///
+/// This runs `setns(2)` on the calling thread, so it is always dispatched to
+/// a dedicated worker thread (via `spawn_blocking`) rather than run inline -
+/// switching the monitor's own network namespace would affect every other
+/// task sharing that thread.
+pub async fn queue_length_for_pid(pid: u32, socket_path_glob: &str) -> Result<i32> {
+    let socket_path_glob = socket_path_glob.to_string();
+
+    tokio::task::spawn_blocking(move || queue_length_in_namespace(pid, &socket_path_glob))
+        .await
+        .context("sock_diag worker thread panicked")?
+}
+
+fn queue_length_in_namespace(pid: u32, socket_path_glob: &str) -> Result<i32> {
+    let original_ns = File::open("/proc/self/ns/net").context("Failed to open current netns")?;
+    let target_ns = File::open(format!("/proc/{}/ns/net", pid))
+        .with_context(|| format!("Failed to open netns of pid {}", pid))?;
+
+    setns(&target_ns).with_context(|| format!("Failed to enter netns of pid {}", pid))?;
+
+    // Always attempt to restore the original namespace, even on error.
+    let result = query_unix_listen_queue(socket_path_glob);
+
+    if let Err(e) = setns(&original_ns) {
+        // We're now stuck in the target namespace; this is fatal for the process.
+        panic!("Failed to restore original netns: {}", e);
+    }
+
+    result
+}
+
+fn setns(ns_file: &File) -> Result<()> {
+    let ret = unsafe { libc::setns(ns_file.as_raw_fd(), libc::CLONE_NEWNET) };
+    if ret != 0 {
+        bail!("setns(2) failed: {}", std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn query_unix_listen_queue(socket_path_glob: &str) -> Result<i32> {
+    let pattern = Pattern::new(socket_path_glob)
+        .with_context(|| format!("Invalid socket path glob '{}'", socket_path_glob))?;
+
+    let sock = unsafe { libc::socket(libc::AF_NETLINK, libc::SOCK_RAW, libc::NETLINK_SOCK_DIAG) };
+    if sock < 0 {
+        bail!("Failed to open NETLINK_SOCK_DIAG socket: {}", std::io::Error::last_os_error());
+    }
+
+    let request = build_request();
+    let sent = unsafe { libc::send(sock, request.as_ptr() as *const _, request.len(), 0) };
+    if sent < 0 {
+        let err = std::io::Error::last_os_error();
+        unsafe { libc::close(sock) };
+        bail!("Failed to send UNIX_DIAG request: {}", err);
+    }
+
+    let result = read_responses(sock, &pattern);
+    unsafe { libc::close(sock) };
+    result
+}
+
+/// Builds an `NLM_F_REQUEST | NLM_F_DUMP` netlink message wrapping a
+/// `unix_diag_req` for all listening `AF_UNIX` sockets, asking for both the
+/// socket name and its receive-queue length.
+fn build_request() -> Vec<u8> {
+    let payload = UnixDiagReq {
+        sdiag_family: libc::AF_UNIX as u8,
+        sdiag_protocol: 0,
+        pad: 0,
+        udiag_states: 1 << TCP_LISTEN,
+        udiag_ino: 0,
+        udiag_show: UDIAG_SHOW_NAME | UDIAG_SHOW_RQLEN,
+        udiag_cookie: [u32::MAX, u32::MAX], // INET_DIAG_NOCOOKIE
+    };
+
+    let payload_bytes = unsafe {
+        std::slice::from_raw_parts(
+            &payload as *const UnixDiagReq as *const u8,
+            mem::size_of::<UnixDiagReq>(),
+        )
+    };
+
+    let total_len = mem::size_of::<libc::nlmsghdr>() + payload_bytes.len();
+    let mut buf = vec![0u8; total_len];
+
+    let header = libc::nlmsghdr {
+        nlmsg_len: total_len as u32,
+        nlmsg_type: SOCK_DIAG_BY_FAMILY,
+        nlmsg_flags: (libc::NLM_F_REQUEST | libc::NLM_F_DUMP) as u16,
+        nlmsg_seq: 1,
+        nlmsg_pid: 0,
+    };
+
+    let header_bytes = unsafe {
+        std::slice::from_raw_parts(
+            &header as *const libc::nlmsghdr as *const u8,
+            mem::size_of::<libc::nlmsghdr>(),
+        )
+    };
+
+    buf[..header_bytes.len()].copy_from_slice(header_bytes);
+    buf[header_bytes.len()..].copy_from_slice(payload_bytes);
+    buf
+}
+
+/// Reads `NETLINK_SOCK_DIAG` dump responses until `NLMSG_DONE`, returning the
+/// receive-queue length of the first `unix_diag_msg` whose `UNIX_DIAG_NAME`
+/// attribute matches `pattern`.
+fn read_responses(sock: i32, pattern: &Pattern) -> Result<i32> {
+    let mut buf = vec![0u8; 16 * 1024];
+
+    loop {
+        let n = unsafe { libc::recv(sock, buf.as_mut_ptr() as *mut _, buf.len(), 0) };
+        if n < 0 {
+            bail!("Failed to read from NETLINK_SOCK_DIAG socket: {}", std::io::Error::last_os_error());
+        }
+        if n == 0 {
+            break;
+        }
+
+        let mut offset = 0usize;
+        let n = n as usize;
+
+        while offset + mem::size_of::<libc::nlmsghdr>() <= n {
+            let header = unsafe { &*(buf.as_ptr().add(offset) as *const libc::nlmsghdr) };
+            let msg_len = header.nlmsg_len as usize;
+            if msg_len == 0 || offset + msg_len > n {
+                break;
+            }
+
+            if header.nlmsg_type as i32 == libc::NLMSG_DONE {
+                return Ok(0);
+            }
+            if header.nlmsg_type as i32 == libc::NLMSG_ERROR {
+                bail!("NETLINK_SOCK_DIAG returned an error response");
+            }
+
+            if let Some(rqueue) = parse_unix_diag_message(&buf[offset..offset + msg_len], pattern) {
+                return Ok(rqueue);
+            }
+
+            offset += align_to_4(msg_len);
+        }
+    }
+
+    Ok(0)
+}
+
+fn parse_unix_diag_message(msg: &[u8], pattern: &Pattern) -> Option<i32> {
+    let header_len = mem::size_of::<libc::nlmsghdr>();
+    let diag_msg_len = mem::size_of::<UnixDiagMsg>();
+    if msg.len() < header_len + diag_msg_len {
+        return None;
+    }
+
+    let mut offset = header_len + diag_msg_len;
+    let mut name: Option<String> = None;
+    let mut rqueue: Option<i32> = None;
+
+    while offset + 4 <= msg.len() {
+        let rta_len = u16::from_ne_bytes([msg[offset], msg[offset + 1]]) as usize;
+        let rta_type = u16::from_ne_bytes([msg[offset + 2], msg[offset + 3]]);
+        if rta_len < 4 || offset + rta_len > msg.len() {
+            break;
+        }
+
+        let content = &msg[offset + 4..offset + rta_len];
+
+        if rta_type == UNIX_DIAG_NAME {
+            name = Some(String::from_utf8_lossy(content).trim_matches('\0').to_string());
+        } else if rta_type == UNIX_DIAG_RQLEN && content.len() >= mem::size_of::<UnixDiagRqlen>() {
+            let rqlen = unsafe { &*(content.as_ptr() as *const UnixDiagRqlen) };
+            rqueue = Some(rqlen.udiag_rqueue as i32);
+        }
+
+        offset += align_to_4(rta_len);
+    }
+
+    match (name, rqueue) {
+        (Some(name), Some(rqueue)) if pattern.matches(&name) => Some(rqueue),
+        _ => None,
+    }
+}
+
+fn align_to_4(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a `unix_diag_msg` netlink record body (header + fixed message +
+    /// attributes) the way the kernel would, for feeding into
+    /// `parse_unix_diag_message` without a real netlink socket.
+    fn build_msg(name: &str, rqueue: Option<u32>) -> Vec<u8> {
+        let mut msg = vec![0u8; mem::size_of::<libc::nlmsghdr>() + mem::size_of::<UnixDiagMsg>()];
+
+        let push_attr = |msg: &mut Vec<u8>, rta_type: u16, content: &[u8]| {
+            let rta_len = 4 + content.len();
+            msg.extend((rta_len as u16).to_ne_bytes());
+            msg.extend(rta_type.to_ne_bytes());
+            msg.extend_from_slice(content);
+            msg.extend(std::iter::repeat_n(0u8, align_to_4(rta_len) - rta_len));
+        };
+
+        push_attr(&mut msg, UNIX_DIAG_NAME, name.as_bytes());
+
+        if let Some(rqueue) = rqueue {
+            let rqlen = UnixDiagRqlen { udiag_rqueue: rqueue, udiag_wqueue: 0 };
+            let content = unsafe {
+                std::slice::from_raw_parts(&rqlen as *const UnixDiagRqlen as *const u8, mem::size_of::<UnixDiagRqlen>())
+            };
+            push_attr(&mut msg, UNIX_DIAG_RQLEN, content);
+        }
+
+        msg
+    }
+
+    #[test]
+    fn parse_extracts_rqueue_for_matching_socket_path() {
+        let msg = build_msg("/var/run/php-fpm/www.socket", Some(7));
+        let pattern = Pattern::new("/var/run/php-fpm/*.socket").unwrap();
+
+        assert_eq!(parse_unix_diag_message(&msg, &pattern), Some(7));
+    }
+
+    #[test]
+    fn parse_returns_none_when_name_does_not_match_glob() {
+        let msg = build_msg("/var/run/other/www.socket", Some(3));
+        let pattern = Pattern::new("/var/run/php-fpm/*.socket").unwrap();
+
+        assert_eq!(parse_unix_diag_message(&msg, &pattern), None);
+    }
+
+    #[test]
+    fn parse_returns_none_without_an_rqlen_attribute() {
+        let msg = build_msg("/var/run/php-fpm/www.socket", None);
+        let pattern = Pattern::new("/var/run/php-fpm/*.socket").unwrap();
+
+        assert_eq!(parse_unix_diag_message(&msg, &pattern), None);
+    }
+
+    #[test]
+    fn parse_returns_none_for_a_truncated_message() {
+        let header_len = mem::size_of::<libc::nlmsghdr>() + mem::size_of::<UnixDiagMsg>();
+        let msg = vec![0u8; header_len - 1];
+        let pattern = Pattern::new("*").unwrap();
+
+        assert_eq!(parse_unix_diag_message(&msg, &pattern), None);
+    }
+
+    #[test]
+    fn align_to_4_rounds_up_to_the_next_multiple_of_four() {
+        assert_eq!(align_to_4(0), 0);
+        assert_eq!(align_to_4(1), 4);
+        assert_eq!(align_to_4(4), 4);
+        assert_eq!(align_to_4(5), 8);
+    }
+}